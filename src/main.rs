@@ -1,4 +1,6 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Block {
@@ -26,10 +28,11 @@ impl Block {
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Internal {
-    Add,
-}
+/// A host-provided native function, boxed so it can live in a `Scope`'s
+/// command registry alongside user-defined blocks. The second argument is
+/// the pipeline input flowing into the call, `Value::Nothing` outside a
+/// pipeline.
+type NativeFn = Rc<dyn Fn(&[Value], &Value) -> Result<Value, EvalError>>;
 
 #[derive(Debug, Clone)]
 pub enum Element {
@@ -39,6 +42,25 @@ pub enum Element {
     Set(String, Box<Element>),
     Block(Block),
     Call(Vec<Element>),
+    If {
+        cond: Box<Element>,
+        then: Block,
+        else_: Option<Block>,
+    },
+    While {
+        cond: Box<Element>,
+        body: Block,
+    },
+    List(Vec<Element>),
+    Index(Box<Element>, Box<Element>),
+    SetIndex {
+        target: Box<Element>,
+        index: Box<Element>,
+        value: Box<Element>,
+    },
+    Pipeline(Vec<Element>),
+    Break,
+    Return(Box<Element>),
 }
 
 impl Element {
@@ -66,6 +88,56 @@ impl Element {
                 }
                 free_variables
             }
+            Element::If { cond, then, else_ } => {
+                let mut free_variables = cond.get_free_variables(known_variables);
+                for elem in &then.commands {
+                    free_variables.extend_from_slice(&elem.get_free_variables(known_variables));
+                }
+                if let Some(else_) = else_ {
+                    for elem in &else_.commands {
+                        free_variables.extend_from_slice(&elem.get_free_variables(known_variables));
+                    }
+                }
+                free_variables
+            }
+            Element::While { cond, body } => {
+                let mut free_variables = cond.get_free_variables(known_variables);
+                for elem in &body.commands {
+                    free_variables.extend_from_slice(&elem.get_free_variables(known_variables));
+                }
+                free_variables
+            }
+            Element::List(elems) => {
+                let mut free_variables = vec![];
+                for elem in elems {
+                    free_variables.extend_from_slice(&elem.get_free_variables(known_variables));
+                }
+                free_variables
+            }
+            Element::Index(target, index) => {
+                let mut free_variables = target.get_free_variables(known_variables);
+                free_variables.extend_from_slice(&index.get_free_variables(known_variables));
+                free_variables
+            }
+            Element::SetIndex {
+                target,
+                index,
+                value,
+            } => {
+                let mut free_variables = target.get_free_variables(known_variables);
+                free_variables.extend_from_slice(&index.get_free_variables(known_variables));
+                free_variables.extend_from_slice(&value.get_free_variables(known_variables));
+                free_variables
+            }
+            Element::Pipeline(stages) => {
+                let mut free_variables = vec![];
+                for stage in stages {
+                    free_variables.extend_from_slice(&stage.get_free_variables(known_variables));
+                }
+                free_variables
+            }
+            Element::Break => vec![],
+            Element::Return(v) => v.get_free_variables(known_variables),
         }
     }
 }
@@ -75,19 +147,57 @@ pub type Captured = HashMap<String, Value>;
 #[derive(Debug, Clone)]
 pub enum Value {
     Nothing,
+    Bool(bool),
     Int(i64),
     String(String),
     Block(Block, Captured),
+    List(Vec<Value>),
 }
 
+/// A byte offset range into the original source text.
+///
+/// No parser exists yet, so every call site below passes `None`; once source
+/// text and a parser land, `Element` can carry a `Span` and this becomes
+/// `Some((start, end))` so errors can point at the offending element.
+pub type Span = (usize, usize);
+
 #[derive(Debug, Clone)]
 enum EvalError {
-    General(String),
+    VariableNotFound(String, Option<Span>),
+    CommandNotFound(String, Option<Span>),
+    ArgMismatch {
+        expected: usize,
+        got: usize,
+        span: Option<Span>,
+    },
+    TypeMismatch {
+        expected: &'static str,
+        got: &'static str,
+        span: Option<Span>,
+    },
+    Arithmetic(String, Option<Span>),
+    /// Non-local control signal: unwinds a `while` loop, caught by the loop itself.
+    Break,
+    /// Non-local control signal: unwinds to the nearest `eval_call` block boundary,
+    /// which converts it into that call's result.
+    Return(Value),
+    /// The call stack exceeded `Scope`'s configured maximum depth, most likely
+    /// because a block recurses into itself without a base case.
+    StackOverflow { depth: usize },
 }
 
+/// Maximum number of nested block calls before `Scope::enter_record` refuses
+/// and `eval_call` reports `EvalError::StackOverflow`. Smaller in debug
+/// builds, where the native stack frames this evaluator itself uses are
+/// larger and overflow sooner.
+#[cfg(debug_assertions)]
+const DEFAULT_MAX_CALL_DEPTH: usize = 64;
+#[cfg(not(debug_assertions))]
+const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+
 struct ScopeRecord {
     variables: HashMap<String, Value>,
-    commands: HashMap<String, Internal>,
+    commands: HashMap<String, NativeFn>,
 }
 impl ScopeRecord {
     pub fn new() -> Self {
@@ -100,11 +210,15 @@ impl ScopeRecord {
 
 struct Scope {
     records: Vec<ScopeRecord>,
+    depth: usize,
+    max_depth: usize,
 }
 impl Scope {
     pub fn new() -> Self {
         Self {
             records: vec![ScopeRecord::new()],
+            depth: 0,
+            max_depth: DEFAULT_MAX_CALL_DEPTH,
         }
     }
     pub fn get_variable(&self, name: &str) -> Option<Value> {
@@ -115,7 +229,7 @@ impl Scope {
         }
         None
     }
-    pub fn get_command(&self, name: &str) -> Option<Internal> {
+    pub fn get_command(&self, name: &str) -> Option<NativeFn> {
         for rec in self.records.iter().rev() {
             if let Some(v) = rec.commands.get(name) {
                 return Some(v.clone());
@@ -128,17 +242,29 @@ impl Scope {
             let _ = rec.variables.insert(name.into(), value);
         }
     }
-    pub fn add_command(&mut self, name: &str, internal: Internal) {
+    /// Register a host-provided native function under `name` so scripts can
+    /// call it like any other command, without the core knowing it exists.
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        f: impl Fn(&[Value], &Value) -> Result<Value, EvalError> + 'static,
+    ) {
         if let Some(rec) = self.records.last_mut() {
-            let _ = rec.commands.insert(name.into(), internal);
+            let _ = rec.commands.insert(name.into(), Rc::new(f));
         }
     }
-    pub fn enter_record(&mut self) {
+    pub fn enter_record(&mut self) -> Result<(), EvalError> {
+        if self.depth >= self.max_depth {
+            return Err(EvalError::StackOverflow { depth: self.depth });
+        }
+        self.depth += 1;
         self.records.push(ScopeRecord::new());
+        Ok(())
     }
     pub fn exit_record(&mut self) {
         if self.records.len() > 1 {
             self.records.pop();
+            self.depth -= 1;
         }
     }
 }
@@ -146,26 +272,30 @@ impl Scope {
 fn eval_block(elems: &[Element], scope: &mut Scope) -> Result<Value, EvalError> {
     let mut output = Value::Nothing;
     for elem in elems {
-        output = eval(elem, scope)?;
+        output = eval(elem, scope, Value::Nothing)?;
     }
 
     Ok(output)
 }
 
-fn eval_call(elems: &[Element], scope: &mut Scope) -> Result<Value, EvalError> {
-    match eval(&elems[0], scope)? {
+fn eval_call(elems: &[Element], scope: &mut Scope, input: Value) -> Result<Value, EvalError> {
+    match eval(&elems[0], scope, Value::Nothing)? {
         Value::Block(b, captured) => {
             // Run the block
             let mut args = vec![];
             for elem in elems.iter().skip(1) {
-                args.push(eval(elem, scope)?);
+                args.push(eval(elem, scope, Value::Nothing)?);
             }
 
             if args.len() != b.params.len() {
-                return Err(EvalError::General("Mismatched number of arguments".into()));
+                return Err(EvalError::ArgMismatch {
+                    expected: b.params.len(),
+                    got: args.len(),
+                    span: None,
+                });
             }
 
-            scope.enter_record();
+            scope.enter_record()?;
 
             // Assign parameters to arguments
             for (arg, param) in args.iter().zip(b.params.iter()) {
@@ -177,40 +307,64 @@ fn eval_call(elems: &[Element], scope: &mut Scope) -> Result<Value, EvalError> {
                 scope.add_variable(&arg, val.clone());
             }
 
+            // Expose the pipeline input flowing into this call, if any.
+            scope.add_variable("$in", input);
+
             // With the frame complete, run the block
             let output = eval_block(&b.commands, scope);
 
             scope.exit_record();
 
-            output
+            // A `Return` unwinds exactly to this block boundary and becomes
+            // the call's result; any other error keeps propagating.
+            match output {
+                Err(EvalError::Return(v)) => Ok(v),
+                other => other,
+            }
         }
         Value::String(s) => {
-            if let Some(i) = scope.get_command(&s) {
-                match i {
-                    Internal::Add => {
-                        // Run the block
-                        let mut args = vec![];
-                        for elem in elems.iter().skip(1) {
-                            args.push(eval(elem, scope)?);
-                        }
-
-                        if args.len() != 2 {
-                            return Err(EvalError::General(
-                                "Mismatched number of arguments".into(),
-                            ));
-                        }
-
-                        match (&args[0], &args[1]) {
-                            (Value::Int(i1), Value::Int(i2)) => Ok(Value::Int(i1 + i2)),
-                            _ => Err(EvalError::General("Add expected integers".into())),
-                        }
-                    }
+            if let Some(f) = scope.get_command(&s) {
+                let mut args = vec![];
+                for elem in elems.iter().skip(1) {
+                    args.push(eval(elem, scope, Value::Nothing)?);
+                }
+
+                f(&args, &input)
+            } else {
+                Err(EvalError::CommandNotFound(s, None))
+            }
+        }
+        _ => Err(EvalError::TypeMismatch {
+            expected: "block or command",
+            got: "value",
+            span: None,
+        }),
+    }
+}
+
+/// Runs one pipeline stage against `input`. A bare call (no explicit
+/// arguments of its own) streams a `Value::List` input element-by-element,
+/// collecting the per-element outputs back into a list; a call with
+/// explicit arguments, or any non-list input, is handed the value whole.
+fn eval_pipeline_stage(
+    stage: &Element,
+    scope: &mut Scope,
+    input: Value,
+) -> Result<Value, EvalError> {
+    match stage {
+        Element::Call(elems) if elems.len() == 1 => {
+            if let Value::List(items) = input {
+                let mut results = vec![];
+                for item in items {
+                    results.push(eval_call(elems, scope, item)?);
                 }
+                Ok(Value::List(results))
             } else {
-                Ok(Value::String("Ran an external command".into()))
+                eval_call(elems, scope, input)
             }
         }
-        _ => Err(EvalError::General("Expected a command block".into())),
+        Element::Call(elems) => eval_call(elems, scope, input),
+        other => eval(other, scope, input),
     }
 }
 
@@ -223,52 +377,326 @@ fn capture_block(b: &Block, scope: &Scope) -> Result<Value, EvalError> {
         if let Some(v) = scope.get_variable(free_variable) {
             captured.insert(free_variable.into(), v.clone());
         } else {
-            return Err(EvalError::General("Unknown variable".into()));
+            return Err(EvalError::VariableNotFound(free_variable.clone(), None));
         }
     }
 
     Ok(Value::Block(b.clone(), captured))
 }
 
-fn eval(element: &Element, scope: &mut Scope) -> Result<Value, EvalError> {
+fn eval(element: &Element, scope: &mut Scope, input: Value) -> Result<Value, EvalError> {
     match element {
         Element::Variable(name) => {
             if let Some(v) = scope.get_variable(&name) {
                 Ok(v)
             } else {
-                Err(EvalError::General("Can not find variable".into()))
+                Err(EvalError::VariableNotFound(name.clone(), None))
             }
         }
         Element::Number(n) => Ok(Value::Int(*n)),
         Element::Set(name, elem) => {
-            if let Ok(v) = eval(elem, scope) {
-                scope.add_variable(name, v);
-                Ok(Value::Nothing)
-            } else {
-                Err(EvalError::General("Failed to eval rhs".into()))
-            }
+            let v = eval(elem, scope, Value::Nothing)?;
+            scope.add_variable(name, v);
+            Ok(Value::Nothing)
         }
-        Element::Call(elems) => eval_call(elems, scope),
+        Element::Call(elems) => eval_call(elems, scope, input),
         Element::Block(b) => Ok(capture_block(b, scope)?),
         Element::Bare(s) => Ok(Value::String(s.clone())),
+        Element::Pipeline(stages) => {
+            let mut current = Value::Nothing;
+            for stage in stages {
+                current = eval_pipeline_stage(stage, scope, current)?;
+            }
+            Ok(current)
+        }
+        Element::If { cond, then, else_ } => match eval(cond, scope, Value::Nothing)? {
+            Value::Bool(true) => eval_block(&then.commands, scope),
+            Value::Bool(false) => {
+                if let Some(else_) = else_ {
+                    eval_block(&else_.commands, scope)
+                } else {
+                    Ok(Value::Nothing)
+                }
+            }
+            _ => Err(EvalError::TypeMismatch {
+                expected: "bool",
+                got: "non-bool",
+                span: None,
+            }),
+        },
+        Element::While { cond, body } => {
+            loop {
+                match eval(cond, scope, Value::Nothing)? {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => break,
+                    _ => {
+                        return Err(EvalError::TypeMismatch {
+                            expected: "bool",
+                            got: "non-bool",
+                            span: None,
+                        })
+                    }
+                }
+
+                match eval_block(&body.commands, scope) {
+                    Ok(_) => {}
+                    Err(EvalError::Break) => break,
+                    Err(e) => return Err(e),
+                }
+            }
+
+            Ok(Value::Nothing)
+        }
+        Element::List(elems) => {
+            let mut values = vec![];
+            for elem in elems {
+                values.push(eval(elem, scope, Value::Nothing)?);
+            }
+            Ok(Value::List(values))
+        }
+        Element::Index(target, index) => {
+            let list = match eval(target, scope, Value::Nothing)? {
+                Value::List(l) => l,
+                _ => {
+                    return Err(EvalError::TypeMismatch {
+                        expected: "list",
+                        got: "non-list",
+                        span: None,
+                    })
+                }
+            };
+            let i = match eval(index, scope, Value::Nothing)? {
+                Value::Int(i) => i,
+                _ => {
+                    return Err(EvalError::TypeMismatch {
+                        expected: "int",
+                        got: "non-int index",
+                        span: None,
+                    })
+                }
+            };
+
+            list_get(&list, i)
+        }
+        Element::SetIndex {
+            target,
+            index,
+            value,
+        } => {
+            let name = match target.as_ref() {
+                Element::Variable(name) => name,
+                _ => {
+                    return Err(EvalError::TypeMismatch {
+                        expected: "variable",
+                        got: "non-variable index target",
+                        span: None,
+                    })
+                }
+            };
+            let mut list = match scope.get_variable(name) {
+                Some(Value::List(l)) => l,
+                Some(_) => {
+                    return Err(EvalError::TypeMismatch {
+                        expected: "list",
+                        got: "non-list",
+                        span: None,
+                    })
+                }
+                None => return Err(EvalError::VariableNotFound(name.clone(), None)),
+            };
+            let i = match eval(index, scope, Value::Nothing)? {
+                Value::Int(i) => i,
+                _ => {
+                    return Err(EvalError::TypeMismatch {
+                        expected: "int",
+                        got: "non-int index",
+                        span: None,
+                    })
+                }
+            };
+            let slot = list_index(&list, i)?;
+            list[slot] = eval(value, scope, Value::Nothing)?;
+
+            scope.add_variable(name, Value::List(list));
+            Ok(Value::Nothing)
+        }
+        Element::Break => Err(EvalError::Break),
+        Element::Return(elem) => {
+            let v = eval(elem, scope, Value::Nothing)?;
+            Err(EvalError::Return(v))
+        }
     }
 }
 
+/// Checks an `i64` index against the list's bounds and returns it as a `Vec`
+/// position, or a `TypeMismatch` if it is negative or past the end.
+fn list_index(list: &[Value], i: i64) -> Result<usize, EvalError> {
+    if i < 0 || i as usize >= list.len() {
+        Err(EvalError::TypeMismatch {
+            expected: "in-bounds index",
+            got: "out-of-range index",
+            span: None,
+        })
+    } else {
+        Ok(i as usize)
+    }
+}
+
+fn list_get(list: &[Value], i: i64) -> Result<Value, EvalError> {
+    let slot = list_index(list, i)?;
+    Ok(list[slot].clone())
+}
+
+/// A binary arithmetic or comparison operator, dispatched by `apply_binop`.
+#[derive(Debug, Clone, Copy)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+/// Single dispatch point for every binary operator, so the type rules live
+/// in one place: int ⊕ int stays int, `Div`/`Rem` by zero is an
+/// `Arithmetic` error, `Add` additionally concatenates strings, and
+/// comparisons require both operands to be the same type.
+fn apply_binop(op: BinOp, lhs: &Value, rhs: &Value) -> Result<Value, EvalError> {
+    match op {
+        BinOp::Add => match (lhs, rhs) {
+            (Value::Int(a), Value::Int(b)) => checked_int(a.checked_add(*b)),
+            (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}"))),
+            _ => Err(EvalError::TypeMismatch {
+                expected: "int or string",
+                got: "mismatched operands",
+                span: None,
+            }),
+        },
+        BinOp::Sub => int_binop(lhs, rhs, |a, b| checked_int(a.checked_sub(b))),
+        BinOp::Mul => int_binop(lhs, rhs, |a, b| checked_int(a.checked_mul(b))),
+        BinOp::Div => int_binop(lhs, rhs, |a, b| {
+            if b == 0 {
+                Err(EvalError::Arithmetic("division by zero".into(), None))
+            } else {
+                checked_int(a.checked_div(b))
+            }
+        }),
+        BinOp::Rem => int_binop(lhs, rhs, |a, b| {
+            if b == 0 {
+                Err(EvalError::Arithmetic("division by zero".into(), None))
+            } else {
+                checked_int(a.checked_rem(b))
+            }
+        }),
+        BinOp::Eq | BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => compare(op, lhs, rhs),
+    }
+}
+
+fn int_binop(
+    lhs: &Value,
+    rhs: &Value,
+    f: impl Fn(i64, i64) -> Result<Value, EvalError>,
+) -> Result<Value, EvalError> {
+    match (lhs, rhs) {
+        (Value::Int(a), Value::Int(b)) => f(*a, *b),
+        _ => Err(EvalError::TypeMismatch {
+            expected: "int",
+            got: "non-int",
+            span: None,
+        }),
+    }
+}
+
+/// Turns a checked-arithmetic result into an `Arithmetic` error on overflow,
+/// so a malicious or buggy script triggers a recoverable error rather than
+/// panicking the whole evaluator (the same `i64::MIN / -1` case that makes
+/// unchecked `/` and `%` panic applies here too).
+fn checked_int(result: Option<i64>) -> Result<Value, EvalError> {
+    match result {
+        Some(n) => Ok(Value::Int(n)),
+        None => Err(EvalError::Arithmetic("integer overflow".into(), None)),
+    }
+}
+
+fn compare(op: BinOp, lhs: &Value, rhs: &Value) -> Result<Value, EvalError> {
+    let ordering = match (lhs, rhs) {
+        (Value::Int(a), Value::Int(b)) => a.cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        _ => {
+            return Err(EvalError::TypeMismatch {
+                expected: "matching types",
+                got: "mismatched operands",
+                span: None,
+            })
+        }
+    };
+
+    let result = match op {
+        BinOp::Eq => ordering == Ordering::Equal,
+        BinOp::Lt => ordering == Ordering::Less,
+        BinOp::Gt => ordering == Ordering::Greater,
+        BinOp::Le => ordering != Ordering::Greater,
+        BinOp::Ge => ordering != Ordering::Less,
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => unreachable!(),
+    };
+
+    Ok(Value::Bool(result))
+}
+
 fn main() {
     let mut scope = Scope::new();
     scope.add_variable("a", Value::Int(10));
-    scope.add_command("add", Internal::Add);
+    for (name, op) in [
+        ("add", BinOp::Add),
+        ("sub", BinOp::Sub),
+        ("mul", BinOp::Mul),
+        ("div", BinOp::Div),
+        ("rem", BinOp::Rem),
+        ("eq", BinOp::Eq),
+        ("lt", BinOp::Lt),
+        ("gt", BinOp::Gt),
+        ("le", BinOp::Le),
+        ("ge", BinOp::Ge),
+    ] {
+        scope.register_fn(name, move |args, _input| {
+            if args.len() != 2 {
+                return Err(EvalError::ArgMismatch {
+                    expected: 2,
+                    got: args.len(),
+                    span: None,
+                });
+            }
+
+            apply_binop(op, &args[0], &args[1])
+        });
+    }
 
-    println!("{:?}", eval(&Element::Number(3), &mut scope));
-    println!("{:?}", eval(&Element::Variable("a".into()), &mut scope));
+    println!(
+        "{:?}",
+        eval(&Element::Number(3), &mut scope, Value::Nothing)
+    );
+    println!(
+        "{:?}",
+        eval(&Element::Variable("a".into()), &mut scope, Value::Nothing)
+    );
     println!(
         "{:?}",
         eval(
             &Element::Set("b".into(), Box::new(Element::Number(11))),
-            &mut scope
+            &mut scope,
+            Value::Nothing,
         )
     );
-    println!("{:?}", eval(&Element::Variable("b".into()), &mut scope));
+    println!(
+        "{:?}",
+        eval(&Element::Variable("b".into()), &mut scope, Value::Nothing)
+    );
 
     let mut block = Block::new();
     block.params = vec!["c".into()];
@@ -278,7 +706,8 @@ fn main() {
         "{:?}",
         eval(
             &Element::Call(vec![Element::Block(block), Element::Number(12)]),
-            &mut scope
+            &mut scope,
+            Value::Nothing,
         )
     );
 
@@ -290,7 +719,8 @@ fn main() {
                 Element::Variable("a".into()),
                 Element::Number(100)
             ]),
-            &mut scope
+            &mut scope,
+            Value::Nothing,
         )
     );
 
@@ -306,7 +736,8 @@ fn main() {
         "{:?}",
         eval(
             &Element::Set("myblock".into(), Box::new(Element::Block(block2))),
-            &mut scope
+            &mut scope,
+            Value::Nothing,
         )
     );
 
@@ -317,7 +748,8 @@ fn main() {
                 Element::Variable("myblock".into()),
                 Element::Number(12)
             ]),
-            &mut scope
+            &mut scope,
+            Value::Nothing,
         )
     );
 
@@ -325,10 +757,14 @@ fn main() {
         "{:?}",
         eval(
             &Element::Set("a".into(), Box::new(Element::Number(1100))),
-            &mut scope
+            &mut scope,
+            Value::Nothing,
         )
     );
-    println!("{:?}", eval(&Element::Variable("a".into()), &mut scope));
+    println!(
+        "{:?}",
+        eval(&Element::Variable("a".into()), &mut scope, Value::Nothing)
+    );
     println!(
         "{:?}",
         eval(
@@ -336,14 +772,283 @@ fn main() {
                 Element::Variable("myblock".into()),
                 Element::Number(12)
             ]),
-            &mut scope
+            &mut scope,
+            Value::Nothing,
         )
     );
     println!(
         "{:?}",
         eval(
             &Element::Call(vec![Element::Variable("myblock".into()),]),
-            &mut scope
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+
+    // Demonstrate while/break: count `i` up until it hits 5, then break early
+    // instead of running the loop out to its condition (10).
+    eval(
+        &Element::Set("i".into(), Box::new(Element::Number(0))),
+        &mut scope,
+        Value::Nothing,
+    )
+    .unwrap();
+
+    let mut break_on_five = Block::new();
+    break_on_five.commands = vec![Element::Break];
+
+    println!(
+        "{:?}",
+        eval(
+            &Element::While {
+                cond: Box::new(Element::Call(vec![
+                    Element::Bare("lt".into()),
+                    Element::Variable("i".into()),
+                    Element::Number(10),
+                ])),
+                body: Block {
+                    params: vec![],
+                    commands: vec![
+                        Element::Set(
+                            "i".into(),
+                            Box::new(Element::Call(vec![
+                                Element::Bare("add".into()),
+                                Element::Variable("i".into()),
+                                Element::Number(1),
+                            ])),
+                        ),
+                        Element::If {
+                            cond: Box::new(Element::Call(vec![
+                                Element::Bare("eq".into()),
+                                Element::Variable("i".into()),
+                                Element::Number(5),
+                            ])),
+                            then: break_on_five,
+                            else_: None,
+                        },
+                    ],
+                },
+            },
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+    println!(
+        "{:?}",
+        eval(&Element::Variable("i".into()), &mut scope, Value::Nothing)
+    );
+
+    // Demonstrate return: a block that exits early through an `If` instead of
+    // running to its last statement, proving `Return` unwinds to the call
+    // boundary rather than just the `If`'s own block.
+    let mut early_return = Block::new();
+    early_return.params = vec!["x".into()];
+    early_return.commands = vec![
+        Element::If {
+            cond: Box::new(Element::Call(vec![
+                Element::Bare("lt".into()),
+                Element::Variable("x".into()),
+                Element::Number(0),
+            ])),
+            then: Block {
+                params: vec![],
+                commands: vec![Element::Return(Box::new(Element::Number(-1)))],
+            },
+            else_: None,
+        },
+        Element::Call(vec![
+            Element::Bare("mul".into()),
+            Element::Variable("x".into()),
+            Element::Number(2),
+        ]),
+    ];
+
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![Element::Block(early_return.clone()), Element::Number(-5)]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![Element::Block(early_return), Element::Number(5)]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+
+    // Demonstrate lists: build one, read an element back by index, then
+    // mutate a slot in place and read it again.
+    eval(
+        &Element::Set(
+            "nums".into(),
+            Box::new(Element::List(vec![
+                Element::Number(1),
+                Element::Number(2),
+                Element::Number(3),
+            ])),
+        ),
+        &mut scope,
+        Value::Nothing,
+    )
+    .unwrap();
+
+    println!(
+        "{:?}",
+        eval(
+            &Element::Index(
+                Box::new(Element::Variable("nums".into())),
+                Box::new(Element::Number(1)),
+            ),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+
+    println!(
+        "{:?}",
+        eval(
+            &Element::SetIndex {
+                target: Box::new(Element::Variable("nums".into())),
+                index: Box::new(Element::Number(1)),
+                value: Box::new(Element::Number(99)),
+            },
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+    println!(
+        "{:?}",
+        eval(
+            &Element::Variable("nums".into()),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+
+    // Demonstrate pipelines: a bare `double` call (no explicit arguments)
+    // streams the list produced by the first stage element-by-element,
+    // receiving each one as its pipeline input rather than the list whole.
+    scope.register_fn("double", |_args, input| {
+        apply_binop(BinOp::Mul, input, &Value::Int(2))
+    });
+
+    println!(
+        "{:?}",
+        eval(
+            &Element::Pipeline(vec![
+                Element::List(vec![
+                    Element::Number(1),
+                    Element::Number(2),
+                    Element::Number(3),
+                ]),
+                Element::Call(vec![Element::Bare("double".into())]),
+            ]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+
+    // Demonstrate the call-depth guard: shrink the limit for the demo, then
+    // nest one block call deeper than it allows.
+    scope.max_depth = 2;
+
+    let mut inner = Block::new();
+    inner.commands = vec![Element::Number(1)];
+
+    let mut middle = Block::new();
+    middle.commands = vec![Element::Call(vec![Element::Block(inner)])];
+
+    let mut outer = Block::new();
+    outer.commands = vec![Element::Call(vec![Element::Block(middle)])];
+
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![Element::Block(outer)]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+
+    scope.max_depth = DEFAULT_MAX_CALL_DEPTH;
+
+    // Demonstrate more of apply_binop's dispatch: mul/rem, a division-by-zero
+    // Arithmetic error, an overflow Arithmetic error, and the gt/le
+    // comparisons (eq/lt are already exercised above; sub and ge are not run).
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![
+                Element::Bare("mul".into()),
+                Element::Number(6),
+                Element::Number(7),
+            ]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![
+                Element::Bare("div".into()),
+                Element::Number(42),
+                Element::Number(0),
+            ]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![
+                Element::Bare("rem".into()),
+                Element::Number(17),
+                Element::Number(5),
+            ]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![
+                Element::Bare("mul".into()),
+                Element::Number(i64::MAX),
+                Element::Number(2),
+            ]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![
+                Element::Bare("gt".into()),
+                Element::Number(3),
+                Element::Number(2),
+            ]),
+            &mut scope,
+            Value::Nothing,
+        )
+    );
+    println!(
+        "{:?}",
+        eval(
+            &Element::Call(vec![
+                Element::Bare("le".into()),
+                Element::Number(2),
+                Element::Number(2),
+            ]),
+            &mut scope,
+            Value::Nothing,
         )
     );
 }